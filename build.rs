@@ -0,0 +1,20 @@
+// Embed app.manifest so the process loads comctl32 v6, which the grouped
+// transition list (LVM_ENABLEGROUPVIEW / collapsible groups) requires. Other
+// toolchains build without it and fall back to a flat list.
+fn main() {
+    println!("cargo:rerun-if-changed=app.manifest");
+
+    let target_os = std::env::var("CARGO_CFG_TARGET_OS").unwrap_or_default();
+    let target_env = std::env::var("CARGO_CFG_TARGET_ENV").unwrap_or_default();
+    if target_os != "windows" || target_env != "msvc" {
+        return;
+    }
+
+    let manifest = std::path::Path::new(&std::env::var("CARGO_MANIFEST_DIR").unwrap())
+        .join("app.manifest");
+    println!("cargo:rustc-link-arg-bins=/MANIFEST:EMBED");
+    println!(
+        "cargo:rustc-link-arg-bins=/MANIFESTINPUT:{}",
+        manifest.display()
+    );
+}
@@ -1,14 +1,19 @@
 #![windows_subsystem = "windows"]
 #![allow(unused_must_use)]
 
-use std::collections::HashSet;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::rc::Rc;
 use windows::{
     core::*,
     Win32::{
         Foundation::*,
         Graphics::Gdi::*,
+        Security::Cryptography::*,
         System::LibraryLoader::GetModuleHandleW,
+        UI::Controls::Dialogs::*,
         UI::Controls::*,
+        UI::Input::KeyboardAndMouse::{EnableWindow, SetFocus},
         UI::WindowsAndMessaging::*,
     },
 };
@@ -49,7 +54,6 @@ impl Symbol {
         }
     }
 
-    #[allow(dead_code)]
     fn from_str(s: &str) -> Option<Symbol> {
         match s.trim() {
             "0" => Some(Symbol::Zero),
@@ -82,6 +86,14 @@ impl Direction {
         }
     }
 
+    fn from_str(s: &str) -> Option<Direction> {
+        match s.trim() {
+            "L" => Some(Direction::Left),
+            "R" => Some(Direction::Right),
+            _ => None,
+        }
+    }
+
     fn index(&self) -> i32 {
         match self {
             Direction::Left => 0,
@@ -100,6 +112,38 @@ struct Transition {
     has_breakpoint: bool,
 }
 
+#[derive(Clone, Debug)]
+struct TraceEntry {
+    step: u64,
+    state: String,
+    head_pos: i64,
+    read_symbol: Symbol,
+    write_symbol: Symbol,
+    direction: Direction,
+    new_state: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum TodoTarget {
+    State(String),
+    Transition(String, Symbol),
+}
+
+impl TodoTarget {
+    fn display(&self) -> String {
+        match self {
+            TodoTarget::State(state) => state.clone(),
+            TodoTarget::Transition(state, sym) => format!("{}, {}", state, sym.display()),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Todo {
+    target: TodoTarget,
+    text: String,
+}
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 enum RunStatus {
     Idle,
@@ -129,12 +173,28 @@ struct TuringMachine {
     reject_state: String,
     transitions: Vec<Transition>,
     state_breakpoints: HashSet<String>,
+    group_view: bool,
+    group_states: Vec<String>,
+    collapsed_groups: HashSet<String>,
+    todos: Vec<Todo>,
+    file_path: Option<String>,
+    locked: bool,
     step_count: u64,
     status: RunStatus,
     timer_speed_ms: u32,
     ui_font: HFONT,
     bold_font: HFONT,
 
+    // Run statistics, cleared on reset
+    trace: VecDeque<TraceEntry>,
+    state_visits: HashMap<String, u64>,
+    transition_hits: HashMap<(String, Symbol), u64>,
+    head_min: i64,
+    head_max: i64,
+
+    // Open tool windows; each holds its own reference to the shared machine
+    tool_windows: Vec<(ToolKind, HWND)>,
+
     // Control handles
     h_listview: HWND,
     h_edit_cur_state: HWND,
@@ -145,6 +205,8 @@ struct TuringMachine {
     h_status_label: HWND,
     h_speed_trackbar: HWND,
     h_state_bp_edit: HWND,
+    h_todo_list: HWND,
+    h_todo_edit: HWND,
 }
 
 impl TuringMachine {
@@ -162,11 +224,23 @@ impl TuringMachine {
             reject_state: "qr".to_string(),
             transitions: Vec::new(),
             state_breakpoints: HashSet::new(),
+            group_view: false,
+            group_states: Vec::new(),
+            collapsed_groups: HashSet::new(),
+            todos: Vec::new(),
+            file_path: None,
+            locked: false,
             step_count: 0,
             status: RunStatus::Idle,
             timer_speed_ms: 500,
             ui_font: HFONT::default(),
             bold_font: HFONT::default(),
+            trace: VecDeque::new(),
+            state_visits: HashMap::new(),
+            transition_hits: HashMap::new(),
+            head_min: 0,
+            head_max: 0,
+            tool_windows: Vec::new(),
             h_listview: HWND::default(),
             h_edit_cur_state: HWND::default(),
             h_combo_read: HWND::default(),
@@ -176,6 +250,8 @@ impl TuringMachine {
             h_status_label: HWND::default(),
             h_speed_trackbar: HWND::default(),
             h_state_bp_edit: HWND::default(),
+            h_todo_list: HWND::default(),
+            h_todo_edit: HWND::default(),
         }
     }
 
@@ -213,6 +289,15 @@ impl TuringMachine {
             .position(|t| t.current_state == state && t.read_symbol == sym)
     }
 
+    fn has_todo(&self, t: &Transition) -> bool {
+        self.todos.iter().any(|todo| match &todo.target {
+            TodoTarget::State(state) => *state == t.current_state,
+            TodoTarget::Transition(state, sym) => {
+                *state == t.current_state && *sym == t.read_symbol
+            }
+        })
+    }
+
     fn step(&mut self) -> bool {
         if self.status == RunStatus::Accepted || self.status == RunStatus::Rejected {
             return false;
@@ -229,6 +314,7 @@ impl TuringMachine {
         let sym = self.read_tape();
         if let Some(idx) = self.find_transition(&self.current_state.clone(), sym) {
             let t = self.transitions[idx].clone();
+            self.record_step(&t);
             self.write_tape(t.write_symbol);
             self.current_state = t.new_state;
             match t.direction {
@@ -236,6 +322,8 @@ impl TuringMachine {
                 Direction::Right => self.head_pos += 1,
             }
             self.step_count += 1;
+            self.head_min = self.head_min.min(self.head_pos);
+            self.head_max = self.head_max.max(self.head_pos);
 
             // Check accept/reject after step
             if self.current_state == self.accept_state {
@@ -266,7 +354,750 @@ impl TuringMachine {
         self.current_state = self.start_state.clone();
         self.step_count = 0;
         self.status = RunStatus::Idle;
+        self.trace.clear();
+        self.state_visits.clear();
+        self.transition_hits.clear();
+        self.head_min = 0;
+        self.head_max = 0;
+    }
+
+    fn record_step(&mut self, t: &Transition) {
+        if self.trace.len() >= TRACE_LIMIT {
+            self.trace.pop_front();
+        }
+        self.trace.push_back(TraceEntry {
+            step: self.step_count + 1,
+            state: t.current_state.clone(),
+            head_pos: self.head_pos,
+            read_symbol: t.read_symbol,
+            write_symbol: t.write_symbol,
+            direction: t.direction,
+            new_state: t.new_state.clone(),
+        });
+        *self
+            .state_visits
+            .entry(t.current_state.clone())
+            .or_insert(0) += 1;
+        *self
+            .transition_hits
+            .entry((t.current_state.clone(), t.read_symbol))
+            .or_insert(0) += 1;
+    }
+}
+
+impl Drop for TuringMachine {
+    // Fonts are shared by every window holding the machine, so they live as
+    // long as the last reference does
+    fn drop(&mut self) {
+        unsafe {
+            if !self.ui_font.is_invalid() {
+                let _ = DeleteObject(self.ui_font);
+            }
+            if !self.bold_font.is_invalid() {
+                let _ = DeleteObject(self.bold_font);
+            }
+        }
+    }
+}
+
+const TRACE_LIMIT: usize = 1000;
+
+// ── Shared Machine ──────────────────────────────────────────────────────────
+//
+// The main window and every tool window store a boxed `Rc` clone in
+// GWLP_USERDATA and release it on WM_NCDESTROY, so no window can outlive the
+// machine it points at. Borrows are taken with `try_borrow*` because message
+// handlers re-enter each other (e.g. SetWindowTextW sends EN_CHANGE).
+
+type SharedMachine = Rc<RefCell<TuringMachine>>;
+
+unsafe fn attach_machine(hwnd: HWND, app: &SharedMachine) {
+    let raw = Box::into_raw(Box::new(app.clone()));
+    SetWindowLongPtrW(hwnd, GWLP_USERDATA, raw as isize);
+}
+
+unsafe fn shared_machine(hwnd: HWND) -> Option<SharedMachine> {
+    let raw = GetWindowLongPtrW(hwnd, GWLP_USERDATA) as *const SharedMachine;
+    if raw.is_null() {
+        None
+    } else {
+        Some((*raw).clone())
+    }
+}
+
+unsafe fn detach_machine(hwnd: HWND) {
+    let raw = SetWindowLongPtrW(hwnd, GWLP_USERDATA, 0) as *mut SharedMachine;
+    if !raw.is_null() {
+        drop(Box::from_raw(raw));
+    }
+}
+
+// ── Machine Files ───────────────────────────────────────────────────────────
+//
+// Plain text, one tab-separated record per line:
+//   version <n>
+//   start/accept/reject <state>
+//   transition <cur> <read> <new> <write> <dir> [bp]
+//   state_bp <state>
+//   todo_state <state> <text>
+//   todo_transition <state> <read> <text>
+//
+// Unknown records are skipped so later additions still open here; a file
+// from a newer format version is refused instead of being half-loaded.
+
+const MACHINE_FILE_VERSION: u32 = 1;
+
+fn sanitize_field(s: &str) -> String {
+    s.replace(['\t', '\r', '\n'], " ")
+}
+
+// State names are record fields, so they can't carry separators
+fn clean_state_name(s: &str) -> String {
+    sanitize_field(s).trim().to_string()
+}
+
+impl TuringMachine {
+    fn serialize(&self) -> String {
+        let mut out = String::new();
+        out.push_str(&format!("version\t{}\n", MACHINE_FILE_VERSION));
+        out.push_str(&format!("start\t{}\n", self.start_state));
+        out.push_str(&format!("accept\t{}\n", self.accept_state));
+        out.push_str(&format!("reject\t{}\n", self.reject_state));
+        for t in &self.transitions {
+            out.push_str(&format!(
+                "transition\t{}\t{}\t{}\t{}\t{}{}\n",
+                t.current_state,
+                t.read_symbol.display(),
+                t.new_state,
+                t.write_symbol.display(),
+                t.direction.display(),
+                if t.has_breakpoint { "\tbp" } else { "" }
+            ));
+        }
+        let mut state_bps: Vec<&String> = self.state_breakpoints.iter().collect();
+        state_bps.sort();
+        for state in state_bps {
+            out.push_str(&format!("state_bp\t{}\n", state));
+        }
+        for todo in &self.todos {
+            let text = sanitize_field(&todo.text);
+            match &todo.target {
+                TodoTarget::State(state) => {
+                    out.push_str(&format!("todo_state\t{}\t{}\n", state, text));
+                }
+                TodoTarget::Transition(state, sym) => {
+                    out.push_str(&format!(
+                        "todo_transition\t{}\t{}\t{}\n",
+                        state,
+                        sym.display(),
+                        text
+                    ));
+                }
+            }
+        }
+        out
+    }
+
+    fn deserialize(&mut self, text: &str) -> std::result::Result<(), String> {
+        let mut loaded = TuringMachine::new();
+
+        for (line_no, line) in text.lines().enumerate() {
+            let line = line.trim_end_matches('\r');
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split('\t').collect();
+            let bad = || format!("Line {}: cannot parse \"{}\"", line_no + 1, line);
+            let state_field = |i: usize| {
+                fields
+                    .get(i)
+                    .map(|f| f.trim().to_string())
+                    .filter(|f| !f.is_empty())
+                    .ok_or_else(bad)
+            };
+            let symbol_field = |i: usize| {
+                fields
+                    .get(i)
+                    .and_then(|f| Symbol::from_str(f))
+                    .ok_or_else(bad)
+            };
+
+            match fields[0] {
+                "version" => {
+                    let version = fields
+                        .get(1)
+                        .and_then(|f| f.trim().parse::<u32>().ok())
+                        .ok_or_else(bad)?;
+                    if version > MACHINE_FILE_VERSION {
+                        return Err(format!(
+                            "Line {}: written by a newer version (format {})",
+                            line_no + 1,
+                            version
+                        ));
+                    }
+                }
+                "start" => loaded.start_state = state_field(1)?,
+                "accept" => loaded.accept_state = state_field(1)?,
+                "reject" => loaded.reject_state = state_field(1)?,
+                "transition" => loaded.transitions.push(Transition {
+                    current_state: state_field(1)?,
+                    read_symbol: symbol_field(2)?,
+                    new_state: state_field(3)?,
+                    write_symbol: symbol_field(4)?,
+                    direction: fields
+                        .get(5)
+                        .and_then(|f| Direction::from_str(f))
+                        .ok_or_else(bad)?,
+                    has_breakpoint: fields.get(6) == Some(&"bp"),
+                }),
+                "state_bp" => {
+                    loaded.state_breakpoints.insert(state_field(1)?);
+                }
+                "todo_state" => loaded.todos.push(Todo {
+                    target: TodoTarget::State(state_field(1)?),
+                    text: fields.get(2).unwrap_or(&"").to_string(),
+                }),
+                "todo_transition" => loaded.todos.push(Todo {
+                    target: TodoTarget::Transition(state_field(1)?, symbol_field(2)?),
+                    text: fields.get(3).unwrap_or(&"").to_string(),
+                }),
+                _ => {}
+            }
+        }
+
+        self.start_state = std::mem::take(&mut loaded.start_state);
+        self.accept_state = std::mem::take(&mut loaded.accept_state);
+        self.reject_state = std::mem::take(&mut loaded.reject_state);
+        self.transitions = std::mem::take(&mut loaded.transitions);
+        self.state_breakpoints = std::mem::take(&mut loaded.state_breakpoints);
+        self.todos = std::mem::take(&mut loaded.todos);
+        // Otherwise refresh_groups reads back the old machine's collapsed groups
+        self.group_states.clear();
+        self.collapsed_groups.clear();
+        self.reset();
+        Ok(())
+    }
+}
+
+// ── Solution Locking ────────────────────────────────────────────────────────
+//
+// A locked file holds a single record, `locked <salt> <iv> <ciphertext> <mac>`
+// (hex), where the ciphertext is the machine file without TODOs or breakpoints,
+// AES-256-CBC encrypted, and the MAC is HMAC-SHA256 over salt, IV and
+// ciphertext. Both keys come from one PBKDF2-SHA256 derivation of the
+// passphrase; the MAC is checked before anything is decrypted.
+
+const LOCK_PBKDF2_ITERATIONS: u64 = 100_000;
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(s: &str) -> Option<Vec<u8>> {
+    s.as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            // from_str_radix alone would also take a sign, e.g. "+f"
+            let pair = std::str::from_utf8(pair)
+                .ok()
+                .filter(|p| p.len() == 2 && p.bytes().all(|b| b.is_ascii_hexdigit()))?;
+            u8::from_str_radix(pair, 16).ok()
+        })
+        .collect()
+}
+
+fn is_locked_file(text: &str) -> bool {
+    text.lines().any(|line| line.starts_with("locked\t"))
+}
+
+// AES key in the first half, HMAC key in the second
+unsafe fn derive_lock_keys(passphrase: &str, salt: &[u8]) -> Option<[u8; 64]> {
+    let mut prf = BCRYPT_ALG_HANDLE::default();
+    if BCryptOpenAlgorithmProvider(
+        &mut prf,
+        BCRYPT_SHA256_ALGORITHM,
+        None,
+        BCRYPT_ALG_HANDLE_HMAC_FLAG,
+    )
+    .is_err()
+    {
+        return None;
+    }
+    let mut key = [0u8; 64];
+    let status = BCryptDeriveKeyPBKDF2(
+        prf,
+        Some(passphrase.as_bytes()),
+        Some(salt),
+        LOCK_PBKDF2_ITERATIONS,
+        &mut key,
+        0,
+    );
+    BCryptCloseAlgorithmProvider(prf, 0);
+    status.is_ok().then_some(key)
+}
+
+unsafe fn lock_mac(mac_key: &[u8], salt: &[u8], iv: &[u8], cipher: &[u8]) -> Option<[u8; 32]> {
+    let mut prf = BCRYPT_ALG_HANDLE::default();
+    if BCryptOpenAlgorithmProvider(
+        &mut prf,
+        BCRYPT_SHA256_ALGORITHM,
+        None,
+        BCRYPT_ALG_HANDLE_HMAC_FLAG,
+    )
+    .is_err()
+    {
+        return None;
+    }
+    let data = [salt, iv, cipher].concat();
+    let mut mac = [0u8; 32];
+    let status = BCryptHash(prf, Some(mac_key), &data, &mut mac);
+    BCryptCloseAlgorithmProvider(prf, 0);
+    status.is_ok().then_some(mac)
+}
+
+unsafe fn aes_cbc(secret: &[u8], iv: &[u8], input: &[u8], encrypt: bool) -> Option<Vec<u8>> {
+    let mut alg = BCRYPT_ALG_HANDLE::default();
+    if BCryptOpenAlgorithmProvider(
+        &mut alg,
+        BCRYPT_AES_ALGORITHM,
+        None,
+        BCRYPT_OPEN_ALGORITHM_PROVIDER_FLAGS(0),
+    )
+    .is_err()
+    {
+        return None;
+    }
+    let mode = to_wide("ChainingModeCBC");
+    let mode_bytes = std::slice::from_raw_parts(mode.as_ptr() as *const u8, mode.len() * 2);
+    let mut key = BCRYPT_KEY_HANDLE::default();
+    let mut output = None;
+    if BCryptSetProperty(alg.into(), BCRYPT_CHAINING_MODE, mode_bytes, 0).is_ok()
+        && BCryptGenerateSymmetricKey(alg, &mut key, None, secret, 0).is_ok()
+    {
+        // The IV buffer is updated in place, so each pass gets a fresh copy
+        let mut len = 0u32;
+        let mut iv_buf = iv.to_vec();
+        let crypt = if encrypt {
+            BCryptEncrypt
+        } else {
+            BCryptDecrypt
+        };
+        let sized = crypt(
+            key,
+            Some(input),
+            None,
+            Some(&mut iv_buf),
+            None,
+            &mut len,
+            BCRYPT_BLOCK_PADDING,
+        );
+        if sized.is_ok() {
+            let mut buf = vec![0u8; len as usize];
+            let mut iv_buf = iv.to_vec();
+            let done = crypt(
+                key,
+                Some(input),
+                None,
+                Some(&mut iv_buf),
+                Some(&mut buf),
+                &mut len,
+                BCRYPT_BLOCK_PADDING,
+            );
+            if done.is_ok() {
+                buf.truncate(len as usize);
+                output = Some(buf);
+            }
+        }
+        BCryptDestroyKey(key);
+    }
+    BCryptCloseAlgorithmProvider(alg, 0);
+    output
+}
+
+impl TuringMachine {
+    // Only what is needed to run it; breakpoints and TODOs would hint at how
+    // the solution works
+    fn solution_text(&self) -> String {
+        let mut solution = TuringMachine::new();
+        solution.start_state = self.start_state.clone();
+        solution.accept_state = self.accept_state.clone();
+        solution.reject_state = self.reject_state.clone();
+        solution.transitions = self
+            .transitions
+            .iter()
+            .map(|t| Transition {
+                has_breakpoint: false,
+                ..t.clone()
+            })
+            .collect();
+        solution.serialize()
+    }
+}
+
+unsafe fn lock_solution(tm: &TuringMachine, passphrase: &str) -> Option<String> {
+    let plain = tm.solution_text();
+
+    let mut salt = [0u8; 16];
+    let mut iv = [0u8; 16];
+    if BCryptGenRandom(None, &mut salt, BCRYPT_USE_SYSTEM_PREFERRED_RNG).is_err()
+        || BCryptGenRandom(None, &mut iv, BCRYPT_USE_SYSTEM_PREFERRED_RNG).is_err()
+    {
+        return None;
+    }
+    let keys = derive_lock_keys(passphrase, &salt)?;
+    let cipher = aes_cbc(&keys[..32], &iv, plain.as_bytes(), true)?;
+    let mac = lock_mac(&keys[32..], &salt, &iv, &cipher)?;
+    Some(format!(
+        "locked\t{}\t{}\t{}\t{}\n",
+        to_hex(&salt),
+        to_hex(&iv),
+        to_hex(&cipher),
+        to_hex(&mac)
+    ))
+}
+
+unsafe fn unlock_solution(text: &str, passphrase: &str) -> std::result::Result<String, String> {
+    let record = text
+        .lines()
+        .find(|line| line.starts_with("locked\t"))
+        .ok_or("Not a locked solution")?;
+    let fields: Vec<&str> = record.trim_end_matches('\r').split('\t').collect();
+    let field = |i: usize| {
+        fields
+            .get(i)
+            .and_then(|f| from_hex(f))
+            .ok_or("Corrupt locked solution")
+    };
+    let (salt, iv, cipher, mac) = (field(1)?, field(2)?, field(3)?, field(4)?);
+    let keys = derive_lock_keys(passphrase, &salt).ok_or("Could not derive the key")?;
+    let expected = lock_mac(&keys[32..], &salt, &iv, &cipher).ok_or("Could not check the file")?;
+    // Compare every byte so the time taken does not depend on where they differ
+    let diff = expected
+        .iter()
+        .zip(&mac)
+        .fold(0u8, |acc, (a, b)| acc | (a ^ b));
+    if mac.len() != expected.len() || diff != 0 {
+        return Err("Wrong passphrase or damaged file".to_string());
+    }
+    let plain = aes_cbc(&keys[..32], &iv, &cipher, false).ok_or("Corrupt locked solution")?;
+    String::from_utf8(plain).map_err(|_| "Corrupt locked solution".to_string())
+}
+
+// ── Excel Export ────────────────────────────────────────────────────────────
+//
+// A minimal SpreadsheetML workbook: inline-string cells, one bold header style,
+// packed into an uncompressed ZIP. Sheet 1 is the transition table, sheet 2 the
+// last run's statistics and trace.
+
+enum Cell {
+    Text(String),
+    Number(i64),
+}
+
+fn text_cell(s: &str) -> Cell {
+    Cell::Text(s.to_string())
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+fn zip_stored(entries: &[(&str, Vec<u8>)]) -> Vec<u8> {
+    const DOS_DATE_1980_01_01: u16 = 0x21;
+    let mut out = Vec::new();
+    let mut central = Vec::new();
+
+    for (name, data) in entries {
+        let offset = out.len() as u32;
+        let crc = crc32(data);
+        let size = data.len() as u32;
+
+        out.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+        out.extend_from_slice(&20u16.to_le_bytes()); // version needed
+        out.extend_from_slice(&0u16.to_le_bytes()); // flags
+        out.extend_from_slice(&0u16.to_le_bytes()); // method: stored
+        out.extend_from_slice(&0u16.to_le_bytes()); // time
+        out.extend_from_slice(&DOS_DATE_1980_01_01.to_le_bytes());
+        out.extend_from_slice(&crc.to_le_bytes());
+        out.extend_from_slice(&size.to_le_bytes());
+        out.extend_from_slice(&size.to_le_bytes());
+        out.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        out.extend_from_slice(&0u16.to_le_bytes()); // extra length
+        out.extend_from_slice(name.as_bytes());
+        out.extend_from_slice(data);
+
+        central.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+        central.extend_from_slice(&20u16.to_le_bytes()); // version made by
+        central.extend_from_slice(&20u16.to_le_bytes()); // version needed
+        central.extend_from_slice(&0u16.to_le_bytes()); // flags
+        central.extend_from_slice(&0u16.to_le_bytes()); // method: stored
+        central.extend_from_slice(&0u16.to_le_bytes()); // time
+        central.extend_from_slice(&DOS_DATE_1980_01_01.to_le_bytes());
+        central.extend_from_slice(&crc.to_le_bytes());
+        central.extend_from_slice(&size.to_le_bytes());
+        central.extend_from_slice(&size.to_le_bytes());
+        central.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        central.extend_from_slice(&[0u8; 12]); // extra, comment, disk, attributes
+        central.extend_from_slice(&offset.to_le_bytes());
+        central.extend_from_slice(name.as_bytes());
+    }
+
+    let central_offset = out.len() as u32;
+    out.extend_from_slice(&central);
+    out.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+    out.extend_from_slice(&[0u8; 4]); // disk numbers
+    out.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    out.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    out.extend_from_slice(&(central.len() as u32).to_le_bytes());
+    out.extend_from_slice(&central_offset.to_le_bytes());
+    out.extend_from_slice(&0u16.to_le_bytes()); // comment length
+    out
+}
+
+fn xml_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\t' | '\n' | '\r' => out.push(c),
+            // Not allowed in XML 1.0 even as character references
+            '\u{0}'..='\u{1f}' | '\u{fffe}' | '\u{ffff}' => {}
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+fn column_name(mut col: usize) -> String {
+    let mut name = Vec::new();
+    loop {
+        name.push(b'A' + (col % 26) as u8);
+        if col < 26 {
+            break;
+        }
+        col = col / 26 - 1;
+    }
+    name.reverse();
+    String::from_utf8(name).unwrap_or_default()
+}
+
+// Header rows are bold, the first `frozen_rows` stay in view when scrolling
+// (none if 0), and widths are in Excel character units
+fn sheet_xml(
+    rows: &[Vec<Cell>],
+    header_rows: &[usize],
+    frozen_rows: usize,
+    widths: &[u32],
+) -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\
+         <worksheet xmlns=\"http://schemas.openxmlformats.org/spreadsheetml/2006/main\">\
+         <sheetViews><sheetView workbookViewId=\"0\">",
+    );
+    if frozen_rows > 0 {
+        xml.push_str(&format!(
+            "<pane ySplit=\"{}\" topLeftCell=\"A{}\" activePane=\"bottomLeft\" state=\"frozen\"/>",
+            frozen_rows,
+            frozen_rows + 1
+        ));
+    }
+    xml.push_str("</sheetView></sheetViews><cols>");
+    for (i, width) in widths.iter().enumerate() {
+        xml.push_str(&format!(
+            "<col min=\"{0}\" max=\"{0}\" width=\"{1}\" customWidth=\"1\"/>",
+            i + 1,
+            width
+        ));
+    }
+    xml.push_str("</cols><sheetData>");
+
+    for (r, row) in rows.iter().enumerate() {
+        xml.push_str(&format!("<row r=\"{}\">", r + 1));
+        let style = if header_rows.contains(&r) {
+            " s=\"1\""
+        } else {
+            ""
+        };
+        for (c, cell) in row.iter().enumerate() {
+            let cell_ref = format!("{}{}", column_name(c), r + 1);
+            match cell {
+                Cell::Text(text) if text.is_empty() => {}
+                Cell::Text(text) => xml.push_str(&format!(
+                    "<c r=\"{}\"{} t=\"inlineStr\"><is><t xml:space=\"preserve\">{}</t></is></c>",
+                    cell_ref,
+                    style,
+                    xml_escape(text)
+                )),
+                Cell::Number(n) => {
+                    xml.push_str(&format!("<c r=\"{}\"{}><v>{}</v></c>", cell_ref, style, n))
+                }
+            }
+        }
+        xml.push_str("</row>");
+    }
+    xml.push_str("</sheetData></worksheet>");
+    xml
+}
+
+fn transition_rows(tm: &TuringMachine) -> Vec<Vec<Cell>> {
+    let mut rows = vec![[
+        "Current State",
+        "Read",
+        "New State",
+        "Write",
+        "Dir",
+        "Breakpoint",
+        "Uses (last run)",
+    ]
+    .iter()
+    .map(|h| text_cell(h))
+    .collect()];
+    if tm.locked {
+        rows.push(vec![text_cell("Transitions hidden for locked solutions")]);
+        return rows;
+    }
+    for t in &tm.transitions {
+        let uses = tm
+            .transition_hits
+            .get(&(t.current_state.clone(), t.read_symbol))
+            .copied()
+            .unwrap_or(0);
+        rows.push(vec![
+            text_cell(&t.current_state),
+            text_cell(t.read_symbol.display()),
+            text_cell(&t.new_state),
+            text_cell(t.write_symbol.display()),
+            text_cell(t.direction.display()),
+            text_cell(if t.has_breakpoint { "yes" } else { "" }),
+            Cell::Number(uses as i64),
+        ]);
+    }
+    rows
+}
+
+fn run_rows(tm: &TuringMachine) -> (Vec<Vec<Cell>>, Vec<usize>) {
+    let mut rows = vec![
+        vec![text_cell("Run Summary")],
+        vec![text_cell("Status"), text_cell(tm.status.display())],
+        vec![text_cell("Steps"), Cell::Number(tm.step_count as i64)],
+        vec![text_cell("Final state"), text_cell(&tm.current_state)],
+        vec![text_cell("Head position"), Cell::Number(tm.head_pos)],
+        vec![text_cell("Leftmost cell"), Cell::Number(tm.head_min)],
+        vec![text_cell("Rightmost cell"), Cell::Number(tm.head_max)],
+        vec![text_cell("Tape"), text_cell(&tape_contents(tm))],
+        vec![],
+    ];
+    let mut headers = vec![0];
+
+    headers.push(rows.len());
+    rows.push(vec![text_cell("State"), text_cell("Steps in state")]);
+    let mut visits: Vec<(&String, &u64)> = tm.state_visits.iter().collect();
+    visits.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+    for (state, n) in visits {
+        rows.push(vec![text_cell(state), Cell::Number(*n as i64)]);
+    }
+    rows.push(vec![]);
+
+    // Each trace row spells out a transition
+    if tm.locked {
+        rows.push(vec![text_cell("Trace hidden for locked solutions")]);
+        return (rows, headers);
+    }
+    let first_step = tm.trace.front().map_or(0, |e| e.step);
+    if first_step > 1 {
+        rows.push(vec![text_cell(&format!(
+            "Trace (last {} of {} steps)",
+            tm.trace.len(),
+            tm.step_count
+        ))]);
     }
+    headers.push(rows.len());
+    rows.push(
+        ["Step", "State", "Head", "Read", "Write", "Dir", "New State"]
+            .iter()
+            .map(|h| text_cell(h))
+            .collect(),
+    );
+    for e in &tm.trace {
+        rows.push(vec![
+            Cell::Number(e.step as i64),
+            text_cell(&e.state),
+            Cell::Number(e.head_pos),
+            text_cell(e.read_symbol.display()),
+            text_cell(e.write_symbol.display()),
+            text_cell(e.direction.display()),
+            text_cell(&e.new_state),
+        ]);
+    }
+    (rows, headers)
+}
+
+fn build_xlsx(tm: &TuringMachine) -> Vec<u8> {
+    const CONTENT_TYPES: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\
+<Types xmlns=\"http://schemas.openxmlformats.org/package/2006/content-types\">\
+<Default Extension=\"rels\" ContentType=\"application/vnd.openxmlformats-package.relationships+xml\"/>\
+<Default Extension=\"xml\" ContentType=\"application/xml\"/>\
+<Override PartName=\"/xl/workbook.xml\" ContentType=\"application/vnd.openxmlformats-officedocument.spreadsheetml.sheet.main+xml\"/>\
+<Override PartName=\"/xl/styles.xml\" ContentType=\"application/vnd.openxmlformats-officedocument.spreadsheetml.styles+xml\"/>\
+<Override PartName=\"/xl/worksheets/sheet1.xml\" ContentType=\"application/vnd.openxmlformats-officedocument.spreadsheetml.worksheet+xml\"/>\
+<Override PartName=\"/xl/worksheets/sheet2.xml\" ContentType=\"application/vnd.openxmlformats-officedocument.spreadsheetml.worksheet+xml\"/>\
+</Types>";
+    const ROOT_RELS: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\
+<Relationships xmlns=\"http://schemas.openxmlformats.org/package/2006/relationships\">\
+<Relationship Id=\"rId1\" Type=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument\" Target=\"xl/workbook.xml\"/>\
+</Relationships>";
+    const WORKBOOK: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\
+<workbook xmlns=\"http://schemas.openxmlformats.org/spreadsheetml/2006/main\" \
+xmlns:r=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships\">\
+<sheets><sheet name=\"Transitions\" sheetId=\"1\" r:id=\"rId1\"/>\
+<sheet name=\"Run Summary\" sheetId=\"2\" r:id=\"rId2\"/></sheets></workbook>";
+    const WORKBOOK_RELS: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\
+<Relationships xmlns=\"http://schemas.openxmlformats.org/package/2006/relationships\">\
+<Relationship Id=\"rId1\" Type=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet\" Target=\"worksheets/sheet1.xml\"/>\
+<Relationship Id=\"rId2\" Type=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet\" Target=\"worksheets/sheet2.xml\"/>\
+<Relationship Id=\"rId3\" Type=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships/styles\" Target=\"styles.xml\"/>\
+</Relationships>";
+    const STYLES: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\
+<styleSheet xmlns=\"http://schemas.openxmlformats.org/spreadsheetml/2006/main\">\
+<fonts count=\"2\"><font><sz val=\"11\"/><name val=\"Calibri\"/></font>\
+<font><b/><sz val=\"11\"/><name val=\"Calibri\"/></font></fonts>\
+<fills count=\"2\"><fill><patternFill patternType=\"none\"/></fill>\
+<fill><patternFill patternType=\"gray125\"/></fill></fills>\
+<borders count=\"1\"><border><left/><right/><top/><bottom/><diagonal/></border></borders>\
+<cellStyleXfs count=\"1\"><xf numFmtId=\"0\" fontId=\"0\" fillId=\"0\" borderId=\"0\"/></cellStyleXfs>\
+<cellXfs count=\"2\"><xf numFmtId=\"0\" fontId=\"0\" fillId=\"0\" borderId=\"0\" xfId=\"0\"/>\
+<xf numFmtId=\"0\" fontId=\"1\" fillId=\"0\" borderId=\"0\" xfId=\"0\" applyFont=\"1\"/></cellXfs>\
+<cellStyles count=\"1\"><cellStyle name=\"Normal\" xfId=\"0\" builtinId=\"0\"/></cellStyles>\
+</styleSheet>";
+
+    let transitions = sheet_xml(&transition_rows(tm), &[0], 1, &[16, 8, 16, 8, 8, 12, 16]);
+    // The summary has several header rows, so nothing is frozen there
+    let (rows, headers) = run_rows(tm);
+    let summary = sheet_xml(&rows, &headers, 0, &[28, 16, 10, 8, 8, 8, 16]);
+
+    zip_stored(&[
+        ("[Content_Types].xml", CONTENT_TYPES.as_bytes().to_vec()),
+        ("_rels/.rels", ROOT_RELS.as_bytes().to_vec()),
+        ("xl/workbook.xml", WORKBOOK.as_bytes().to_vec()),
+        (
+            "xl/_rels/workbook.xml.rels",
+            WORKBOOK_RELS.as_bytes().to_vec(),
+        ),
+        ("xl/styles.xml", STYLES.as_bytes().to_vec()),
+        ("xl/worksheets/sheet1.xml", transitions.into_bytes()),
+        ("xl/worksheets/sheet2.xml", summary.into_bytes()),
+    ])
 }
 
 // ── Control IDs ─────────────────────────────────────────────────────────────
@@ -289,6 +1120,20 @@ const ID_TRACKBAR: i32 = 1030;
 const ID_STATE_BP_EDIT: i32 = 1031;
 const ID_BTN_ADD_STATE_BP: i32 = 1032;
 const ID_STATUS_LABEL: i32 = 1040;
+const ID_BTN_SAVE: i32 = 1050;
+const ID_BTN_OPEN: i32 = 1051;
+const ID_BTN_EXPORT_LOCKED: i32 = 1052;
+const ID_BTN_EXPORT_XLSX: i32 = 1053;
+const ID_TODO_LIST: i32 = 1060;
+const ID_TODO_EDIT: i32 = 1061;
+const ID_BTN_TODO_ROW: i32 = 1062;
+const ID_BTN_TODO_STATE: i32 = 1063;
+const ID_BTN_TODO_DONE: i32 = 1064;
+const ID_PASSPHRASE_EDIT: i32 = 1070;
+const ID_BTN_TOOL_TRACE: i32 = 1080;
+const ID_BTN_TOOL_DIAGRAM: i32 = 1081;
+const ID_BTN_TOOL_STATS: i32 = 1082;
+const ID_BTN_TOOL_WATCH: i32 = 1083;
 const ID_TIMER: usize = 9001;
 
 // ── Custom Draw structures ──────────────────────────────────────────────────
@@ -317,14 +1162,14 @@ unsafe extern "system" fn wndproc(
     wparam: WPARAM,
     lparam: LPARAM,
 ) -> LRESULT {
-    let tm_ptr = GetWindowLongPtrW(hwnd, GWLP_USERDATA) as *mut TuringMachine;
+    let app = shared_machine(hwnd);
 
     match msg {
         WM_PAINT => {
-            if tm_ptr.is_null() {
+            let Some(mut tm) = app.as_ref().and_then(|a| a.try_borrow_mut().ok()) else {
                 return DefWindowProcW(hwnd, msg, wparam, lparam);
-            }
-            let tm = &mut *tm_ptr;
+            };
+            let tm = &mut *tm;
             let mut ps = PAINTSTRUCT::default();
             let hdc = BeginPaint(hwnd, &mut ps);
 
@@ -410,13 +1255,52 @@ unsafe extern "system" fn wndproc(
         }
 
         WM_COMMAND => {
-            if tm_ptr.is_null() {
+            let Some(app) = app else {
                 return DefWindowProcW(hwnd, msg, wparam, lparam);
-            }
-            let tm = &mut *tm_ptr;
+            };
             let cmd = (wparam.0 & 0xffff) as i32;
             let notification = ((wparam.0 >> 16) & 0xffff) as u32;
 
+            // Locked solutions can be run but not viewed, edited or re-saved
+            let locked = app.try_borrow().map(|tm| tm.locked).unwrap_or(false);
+            if locked
+                && matches!(
+                    cmd,
+                    ID_BTN_ADD
+                        | ID_BTN_UPDATE
+                        | ID_BTN_DELETE
+                        | ID_BTN_TOGGLE_BP
+                        | ID_BTN_TODO_ROW
+                        | ID_BTN_TODO_STATE
+                        | ID_BTN_SAVE
+                        | ID_BTN_EXPORT_LOCKED
+                )
+            {
+                show_error(
+                    hwnd,
+                    "This machine is a locked solution and cannot be edited or saved.",
+                );
+                return LRESULT(0);
+            }
+
+            // These open modal dialogs, so they manage their own borrows
+            match cmd {
+                ID_BTN_SAVE | ID_BTN_EXPORT_LOCKED | ID_BTN_EXPORT_XLSX | ID_BTN_OPEN => {
+                    run_file_command(hwnd, &app, cmd);
+                    return LRESULT(0);
+                }
+                _ => {}
+            }
+            if let Some(kind) = ToolKind::from_command(cmd) {
+                toggle_tool_window(hwnd, &app, kind);
+                return LRESULT(0);
+            }
+
+            let Ok(mut tm) = app.try_borrow_mut() else {
+                return LRESULT(0);
+            };
+            let tm = &mut *tm;
+
             match cmd {
                 ID_BTN_ADD => {
                     if let Some(t) = read_transition_from_editor(tm) {
@@ -484,8 +1368,7 @@ unsafe extern "system" fn wndproc(
                     }
                 }
                 ID_BTN_ADD_STATE_BP => {
-                    let state = get_window_text(tm.h_state_bp_edit);
-                    let state = state.trim().to_string();
+                    let state = clean_state_name(&get_window_text(tm.h_state_bp_edit));
                     if !state.is_empty() {
                         if tm.state_breakpoints.contains(&state) {
                             tm.state_breakpoints.remove(&state);
@@ -496,6 +1379,42 @@ unsafe extern "system" fn wndproc(
                         update_status(tm);
                     }
                 }
+                ID_BTN_TODO_ROW => {
+                    let sel = get_listview_selection(tm.h_listview);
+                    let text = get_window_text(tm.h_todo_edit);
+                    if sel >= 0 && (sel as usize) < tm.transitions.len() && !text.trim().is_empty()
+                    {
+                        let t = &tm.transitions[sel as usize];
+                        tm.todos.push(Todo {
+                            target: TodoTarget::Transition(t.current_state.clone(), t.read_symbol),
+                            text: text.trim().to_string(),
+                        });
+                        SetWindowTextW(tm.h_todo_edit, w!(""));
+                        refresh_todos(tm);
+                        refresh_listview(tm);
+                    }
+                }
+                ID_BTN_TODO_STATE => {
+                    let state = clean_state_name(&get_window_text(tm.h_edit_cur_state));
+                    let text = get_window_text(tm.h_todo_edit);
+                    if !state.is_empty() && !text.trim().is_empty() {
+                        tm.todos.push(Todo {
+                            target: TodoTarget::State(state),
+                            text: text.trim().to_string(),
+                        });
+                        SetWindowTextW(tm.h_todo_edit, w!(""));
+                        refresh_todos(tm);
+                        refresh_listview(tm);
+                    }
+                }
+                ID_BTN_TODO_DONE => {
+                    let sel = SendMessageW(tm.h_todo_list, LB_GETCURSEL, WPARAM(0), LPARAM(0)).0;
+                    if sel >= 0 && (sel as usize) < tm.todos.len() {
+                        tm.todos.remove(sel as usize);
+                        refresh_todos(tm);
+                        refresh_listview(tm);
+                    }
+                }
                 _ => {
                     // Handle ListView item click via notification
                     if notification == LBN_SELCHANGE as u32 {
@@ -507,10 +1426,10 @@ unsafe extern "system" fn wndproc(
         }
 
         WM_NOTIFY => {
-            if tm_ptr.is_null() {
+            let Some(mut tm) = app.as_ref().and_then(|a| a.try_borrow_mut().ok()) else {
                 return DefWindowProcW(hwnd, msg, wparam, lparam);
-            }
-            let tm = &mut *tm_ptr;
+            };
+            let tm = &mut *tm;
             let nmhdr = *(lparam.0 as *const NMHDR);
 
             if nmhdr.hwndFrom == tm.h_listview {
@@ -555,10 +1474,10 @@ unsafe extern "system" fn wndproc(
         }
 
         WM_TIMER => {
-            if tm_ptr.is_null() {
+            let Some(mut tm) = app.as_ref().and_then(|a| a.try_borrow_mut().ok()) else {
                 return LRESULT(0);
-            }
-            let tm = &mut *tm_ptr;
+            };
+            let tm = &mut *tm;
             if wparam.0 == ID_TIMER {
                 let can_continue = tm.step();
                 update_status(tm);
@@ -575,10 +1494,10 @@ unsafe extern "system" fn wndproc(
         }
 
         WM_HSCROLL => {
-            if tm_ptr.is_null() {
+            let Some(mut tm) = app.as_ref().and_then(|a| a.try_borrow_mut().ok()) else {
                 return LRESULT(0);
-            }
-            let tm = &mut *tm_ptr;
+            };
+            let tm = &mut *tm;
             let ctrl = HWND(lparam.0 as isize);
             if ctrl == tm.h_speed_trackbar {
                 const TBM_GETPOS_MSG: u32 = 0x0400; // WM_USER + 0
@@ -593,33 +1512,136 @@ unsafe extern "system" fn wndproc(
             return LRESULT(0);
         }
 
-        WM_DESTROY => {
-            if !tm_ptr.is_null() {
-                let tm = &mut *tm_ptr;
-                if !tm.ui_font.is_invalid() {
-                    let _ = DeleteObject(tm.ui_font);
-                }
-                if !tm.bold_font.is_invalid() {
-                    let _ = DeleteObject(tm.bold_font);
-                }
-                let _ = Box::from_raw(tm_ptr); // Free TM
+        WM_CLOSE => {
+            // Owned tool windows are destroyed before WM_DESTROY reaches us
+            if let Some(tm) = app.as_ref().and_then(|a| a.try_borrow().ok()) {
+                save_tool_layout(&tm);
             }
+        }
+
+        WM_DESTROY => {
             PostQuitMessage(0);
             return LRESULT(0);
         }
 
+        WM_NCDESTROY => {
+            detach_machine(hwnd); // Tool windows may still hold the machine
+        }
+
         _ => {}
     }
 
     DefWindowProcW(hwnd, msg, wparam, lparam)
 }
 
+unsafe fn run_file_command(hwnd: HWND, app: &SharedMachine, cmd: i32) {
+    let (current_path, font) = {
+        let tm = app.borrow();
+        (tm.file_path.clone(), tm.ui_font)
+    };
+
+    match cmd {
+        ID_BTN_SAVE => {
+            if let Some(path) = prompt_machine_file(hwnd, current_path.as_deref(), true) {
+                let text = app.borrow().serialize();
+                match std::fs::write(&path, text) {
+                    Ok(()) => {
+                        let mut tm = app.borrow_mut();
+                        tm.file_path = Some(path);
+                        save_tool_layout(&tm);
+                    }
+                    Err(e) => show_error(hwnd, &format!("Could not save {}: {}", path, e)),
+                }
+            }
+        }
+        ID_BTN_EXPORT_LOCKED => {
+            let Some(passphrase) = prompt_passphrase(hwnd, "Passphrase for locked solution:", font)
+            else {
+                return;
+            };
+            if passphrase.is_empty() {
+                show_error(hwnd, "A passphrase is required to lock a solution.");
+                return;
+            }
+            // A typo here would lock the solution for good
+            let Some(confirm) = prompt_passphrase(hwnd, "Confirm passphrase:", font) else {
+                return;
+            };
+            if confirm != passphrase {
+                show_error(hwnd, "The passphrases do not match.");
+            } else if let Some(path) = prompt_machine_file(hwnd, None, true) {
+                let locked = lock_solution(&app.borrow(), &passphrase);
+                match locked {
+                    Some(text) => {
+                        if let Err(e) = std::fs::write(&path, text) {
+                            show_error(hwnd, &format!("Could not save {}: {}", path, e));
+                        }
+                    }
+                    None => show_error(hwnd, "Could not encrypt the solution."),
+                }
+            }
+        }
+        ID_BTN_EXPORT_XLSX => {
+            // Default to the machine's name with an .xlsx extension
+            let suggested = current_path.as_deref().map(|p| {
+                std::path::Path::new(p)
+                    .with_extension("xlsx")
+                    .to_string_lossy()
+                    .into_owned()
+            });
+            let filter = "Excel workbooks (*.xlsx)\0*.xlsx\0All files (*.*)\0*.*\0";
+            if let Some(path) = prompt_file(hwnd, suggested.as_deref(), true, filter, w!("xlsx")) {
+                let data = build_xlsx(&app.borrow());
+                if let Err(e) = std::fs::write(&path, data) {
+                    show_error(hwnd, &format!("Could not save {}: {}", path, e));
+                }
+            }
+        }
+        ID_BTN_OPEN => {
+            let Some(path) = prompt_machine_file(hwnd, current_path.as_deref(), false) else {
+                return;
+            };
+            let mut locked = false;
+            let loaded = std::fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|text| {
+                    if !is_locked_file(&text) {
+                        return Ok(text);
+                    }
+                    locked = true;
+                    let passphrase =
+                        prompt_passphrase(hwnd, "Passphrase for locked solution:", font)
+                            .ok_or("A passphrase is required to open a locked solution")?;
+                    unlock_solution(&text, &passphrase)
+                })
+                .and_then(|text| app.borrow_mut().deserialize(&text));
+            match loaded {
+                Ok(()) => {
+                    KillTimer(hwnd, ID_TIMER);
+                    let mut tm = app.borrow_mut();
+                    save_tool_layout(&tm); // Still keyed by the previous file
+                    tm.locked = locked;
+                    tm.file_path = Some(path);
+                    refresh_listview(&mut tm);
+                    refresh_todos(&tm);
+                    update_status(&tm);
+                    InvalidateRect(hwnd, None, true);
+                    drop(tm);
+                    restore_tool_layout(hwnd, app);
+                }
+                Err(e) => show_error(hwnd, &format!("Could not open {}: {}", path, e)),
+            }
+        }
+        _ => {}
+    }
+}
+
 // ── UI Helpers ──────────────────────────────────────────────────────────────
 
 unsafe fn read_transition_from_editor(tm: &TuringMachine) -> Option<Transition> {
-    let cur_state = get_window_text(tm.h_edit_cur_state);
-    let new_state = get_window_text(tm.h_edit_new_state);
-    if cur_state.trim().is_empty() || new_state.trim().is_empty() {
+    let cur_state = clean_state_name(&get_window_text(tm.h_edit_cur_state));
+    let new_state = clean_state_name(&get_window_text(tm.h_edit_new_state));
+    if cur_state.is_empty() || new_state.is_empty() {
         return None;
     }
 
@@ -635,9 +1657,9 @@ unsafe fn read_transition_from_editor(tm: &TuringMachine) -> Option<Transition>
     let dirs = [Direction::Left, Direction::Right];
 
     Some(Transition {
-        current_state: cur_state.trim().to_string(),
+        current_state: cur_state,
         read_symbol: symbols[read_idx as usize],
-        new_state: new_state.trim().to_string(),
+        new_state,
         write_symbol: symbols[write_idx as usize],
         direction: dirs[dir_idx as usize],
         has_breakpoint: false,
@@ -675,17 +1697,100 @@ unsafe fn get_listview_selection(hlv: HWND) -> i32 {
         as i32
 }
 
-unsafe fn refresh_listview(tm: &TuringMachine) {
+unsafe fn refresh_groups(tm: &mut TuringMachine) {
+    // Remember which groups the user collapsed before rebuilding them
+    for (i, state) in tm.group_states.iter().enumerate() {
+        let collapsed = SendMessageW(
+            tm.h_listview,
+            LVM_GETGROUPSTATE,
+            WPARAM(i + 1),
+            LPARAM(LVGS_COLLAPSED.0 as isize),
+        );
+        if collapsed.0 != 0 {
+            tm.collapsed_groups.insert(state.clone());
+        } else {
+            tm.collapsed_groups.remove(state);
+        }
+    }
+    SendMessageW(tm.h_listview, LVM_REMOVEALLGROUPS, WPARAM(0), LPARAM(0));
+
+    // One group per current state, in order of first appearance
+    tm.group_states.clear();
+    for t in &tm.transitions {
+        if !tm.group_states.contains(&t.current_state) {
+            tm.group_states.push(t.current_state.clone());
+        }
+    }
+
+    for (i, state) in tm.group_states.iter().enumerate() {
+        let count = tm
+            .transitions
+            .iter()
+            .filter(|t| &t.current_state == state)
+            .count();
+        let header = format!(
+            "{}  ({} transition{})",
+            state,
+            count,
+            if count == 1 { "" } else { "s" }
+        );
+        let w_header = to_wide(&header);
+        let mut group_state = LVGS_COLLAPSIBLE;
+        if tm.collapsed_groups.contains(state) {
+            group_state |= LVGS_COLLAPSED;
+        }
+        let group = LVGROUP {
+            cbSize: std::mem::size_of::<LVGROUP>() as u32,
+            mask: LVGF_HEADER | LVGF_GROUPID | LVGF_STATE | LVGF_ALIGN,
+            pszHeader: PWSTR(w_header.as_ptr() as *mut u16),
+            iGroupId: i as i32 + 1,
+            stateMask: LVGS_COLLAPSIBLE | LVGS_COLLAPSED,
+            state: group_state,
+            uAlign: LVGA_HEADER_LEFT,
+            ..Default::default()
+        };
+        SendMessageW(
+            tm.h_listview,
+            LVM_INSERTGROUP,
+            WPARAM(usize::MAX),
+            LPARAM(&group as *const _ as isize),
+        );
+    }
+}
+
+fn group_id_for(tm: &TuringMachine, state: &str) -> i32 {
+    tm.group_states
+        .iter()
+        .position(|s| s == state)
+        .map_or(0, |i| i as i32 + 1)
+}
+
+unsafe fn refresh_listview(tm: &mut TuringMachine) {
+    invalidate_tool_windows(tm); // The diagram follows the table
     SendMessageW(tm.h_listview, LVM_DELETEALLITEMS, WPARAM(0), LPARAM(0));
+    if tm.locked {
+        // Keep the construction of locked solutions out of view
+        SendMessageW(tm.h_listview, LVM_REMOVEALLGROUPS, WPARAM(0), LPARAM(0));
+        tm.group_states.clear();
+        return;
+    }
+    // Without comctl32 v6 the list stays flat
+    let mut mask = LVIF_TEXT | LVIF_IMAGE;
+    if tm.group_view {
+        refresh_groups(tm);
+        mask |= LVIF_GROUPID;
+    }
 
     for (i, t) in tm.transitions.iter().enumerate() {
-        // Insert item (column 0)
+        // Insert item (column 0) into its state's group
         let cs = to_wide(&t.current_state);
         let mut lvi = LVITEMW {
-            mask: LVIF_TEXT,
+            mask,
             iItem: i as i32,
             iSubItem: 0,
             pszText: PWSTR(cs.as_ptr() as *mut u16),
+            iImage: if tm.has_todo(t) { 0 } else { I_IMAGENONE },
+            iGroupId: group_id_for(tm, &t.current_state),
             ..Default::default()
         };
         SendMessageW(
@@ -697,6 +1802,7 @@ unsafe fn refresh_listview(tm: &TuringMachine) {
 
         // Read symbol (column 1)
         let rs = to_wide(t.read_symbol.display());
+        lvi.mask = LVIF_TEXT;
         lvi.iSubItem = 1;
         lvi.pszText = PWSTR(rs.as_ptr() as *mut u16);
         SendMessageW(
@@ -741,9 +1847,205 @@ unsafe fn refresh_listview(tm: &TuringMachine) {
     }
 }
 
-unsafe fn update_status(tm: &TuringMachine) {
-    let bp_list: Vec<&String> = tm.state_breakpoints.iter().collect();
-    let bp_str = if bp_list.is_empty() {
+unsafe fn refresh_todos(tm: &TuringMachine) {
+    SendMessageW(tm.h_todo_list, LB_RESETCONTENT, WPARAM(0), LPARAM(0));
+    for todo in &tm.todos {
+        let line = format!("[{}] {}", todo.target.display(), todo.text);
+        let w = to_wide(&line);
+        SendMessageW(
+            tm.h_todo_list,
+            LB_ADDSTRING,
+            WPARAM(0),
+            LPARAM(w.as_ptr() as isize),
+        );
+    }
+}
+
+unsafe fn prompt_machine_file(hwnd: HWND, current: Option<&str>, save: bool) -> Option<String> {
+    let filter = "Turing machines (*.tm)\0*.tm\0All files (*.*)\0*.*\0";
+    prompt_file(hwnd, current, save, filter, w!("tm"))
+}
+
+unsafe fn prompt_file(
+    hwnd: HWND,
+    current: Option<&str>,
+    save: bool,
+    filter: &str,
+    default_ext: PCWSTR,
+) -> Option<String> {
+    let mut file_buf = [0u16; 260];
+    if let Some(current) = current {
+        let w = to_wide(current);
+        if w.len() <= file_buf.len() {
+            file_buf[..w.len()].copy_from_slice(&w);
+        }
+    }
+    let filter = to_wide(filter);
+    let mut ofn = OPENFILENAMEW {
+        lStructSize: std::mem::size_of::<OPENFILENAMEW>() as u32,
+        hwndOwner: hwnd,
+        lpstrFilter: PCWSTR(filter.as_ptr()),
+        lpstrFile: PWSTR(file_buf.as_mut_ptr()),
+        nMaxFile: file_buf.len() as u32,
+        lpstrDefExt: default_ext,
+        ..Default::default()
+    };
+    let ok = if save {
+        ofn.Flags = OFN_OVERWRITEPROMPT | OFN_PATHMUSTEXIST;
+        GetSaveFileNameW(&mut ofn)
+    } else {
+        ofn.Flags = OFN_FILEMUSTEXIST | OFN_PATHMUSTEXIST;
+        GetOpenFileNameW(&mut ofn)
+    };
+    if !ok.as_bool() {
+        return None;
+    }
+    let len = file_buf
+        .iter()
+        .position(|&c| c == 0)
+        .unwrap_or(file_buf.len());
+    Some(String::from_utf16_lossy(&file_buf[..len]))
+}
+
+// ── Passphrase Prompt ───────────────────────────────────────────────────────
+
+struct PassphrasePrompt {
+    h_edit: HWND,
+    result: Option<String>,
+    done: bool,
+}
+
+unsafe extern "system" fn passphrase_wndproc(
+    hwnd: HWND,
+    msg: u32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    let prompt_ptr = GetWindowLongPtrW(hwnd, GWLP_USERDATA) as *mut PassphrasePrompt;
+    if prompt_ptr.is_null() {
+        return DefWindowProcW(hwnd, msg, wparam, lparam);
+    }
+    let prompt = &mut *prompt_ptr;
+
+    match msg {
+        WM_COMMAND => {
+            let cmd = (wparam.0 & 0xffff) as i32;
+            if cmd == IDOK.0 {
+                prompt.result = Some(get_window_text(prompt.h_edit));
+                prompt.done = true;
+            } else if cmd == IDCANCEL.0 {
+                prompt.done = true;
+            }
+            return LRESULT(0);
+        }
+        WM_CLOSE => {
+            prompt.done = true;
+            return LRESULT(0);
+        }
+        _ => {}
+    }
+
+    DefWindowProcW(hwnd, msg, wparam, lparam)
+}
+
+// None if the user cancels
+unsafe fn prompt_passphrase(owner: HWND, label: &str, font: HFONT) -> Option<String> {
+    let hinst: HINSTANCE = GetModuleHandleW(None).ok()?.into();
+    let class_name = w!("PassphrasePromptClass");
+    let wc = WNDCLASSW {
+        lpfnWndProc: Some(passphrase_wndproc),
+        hInstance: hinst,
+        lpszClassName: class_name,
+        hbrBackground: HBRUSH((COLOR_BTNFACE.0 + 1) as isize),
+        hCursor: LoadCursorW(None, IDC_ARROW).ok()?,
+        ..Default::default()
+    };
+    RegisterClassW(&wc); // Fails harmlessly once the class exists
+
+    let mut owner_rc = RECT::default();
+    GetWindowRect(owner, &mut owner_rc);
+    let hwnd = CreateWindowExW(
+        WS_EX_DLGMODALFRAME,
+        class_name,
+        w!("Locked Solution"),
+        WS_POPUP | WS_CAPTION | WS_SYSMENU | WS_VISIBLE,
+        owner_rc.left + 200,
+        owner_rc.top + 150,
+        340,
+        150,
+        owner,
+        None,
+        hinst,
+        None,
+    );
+
+    create_static(hwnd, hinst, label, 10, 10, 300, 18, font);
+    let h_edit = CreateWindowExW(
+        WS_EX_CLIENTEDGE,
+        w!("EDIT"),
+        w!(""),
+        WINDOW_STYLE(
+            WS_CHILD.0 | WS_VISIBLE.0 | WS_TABSTOP.0 | ES_PASSWORD as u32 | ES_AUTOHSCROLL as u32,
+        ),
+        10,
+        32,
+        305,
+        24,
+        hwnd,
+        HMENU(ID_PASSPHRASE_EDIT as isize),
+        hinst,
+        None,
+    );
+    send_font(h_edit, font);
+    create_button(hwnd, hinst, "OK", 150, 66, 80, 28, IDOK.0, font);
+    create_button(hwnd, hinst, "Cancel", 235, 66, 80, 28, IDCANCEL.0, font);
+
+    let prompt_ptr = Box::into_raw(Box::new(PassphrasePrompt {
+        h_edit,
+        result: None,
+        done: false,
+    }));
+    SetWindowLongPtrW(hwnd, GWLP_USERDATA, prompt_ptr as isize);
+    EnableWindow(owner, false);
+    SetFocus(h_edit);
+
+    let mut msg = MSG::default();
+    loop {
+        // Set by passphrase_wndproc while messages are dispatched
+        if (*prompt_ptr).done {
+            break;
+        }
+        if !GetMessageW(&mut msg, None, 0, 0).as_bool() {
+            // Hand WM_QUIT back to the main loop
+            PostQuitMessage(msg.wParam.0 as i32);
+            break;
+        }
+        if !IsDialogMessageW(hwnd, &msg).as_bool() {
+            let _ = TranslateMessage(&msg);
+            DispatchMessageW(&msg);
+        }
+    }
+
+    EnableWindow(owner, true);
+    SetWindowLongPtrW(hwnd, GWLP_USERDATA, 0);
+    DestroyWindow(hwnd);
+    SetForegroundWindow(owner);
+    Box::from_raw(prompt_ptr).result
+}
+
+unsafe fn show_error(hwnd: HWND, text: &str) {
+    let w = to_wide(text);
+    MessageBoxW(
+        hwnd,
+        PCWSTR(w.as_ptr()),
+        w!("Turing Machine Simulator"),
+        MB_OK | MB_ICONERROR,
+    );
+}
+
+unsafe fn update_status(tm: &TuringMachine) {
+    let bp_list: Vec<&String> = tm.state_breakpoints.iter().collect();
+    let bp_str = if bp_list.is_empty() {
         String::new()
     } else {
         format!(
@@ -756,14 +2058,540 @@ unsafe fn update_status(tm: &TuringMachine) {
         )
     };
     let text = format!(
-        "State: {}  |  Steps: {}  |  Status: {}{}",
+        "State: {}  |  Steps: {}  |  Status: {}{}{}",
         tm.current_state,
         tm.step_count,
         tm.status.display(),
-        bp_str
+        bp_str,
+        if tm.locked {
+            "   (Locked solution)"
+        } else {
+            ""
+        }
     );
     let w = to_wide(&text);
     SetWindowTextW(tm.h_status_label, PCWSTR(w.as_ptr()));
+    invalidate_tool_windows(tm);
+}
+
+// ── Tool Windows ────────────────────────────────────────────────────────────
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ToolKind {
+    Trace,
+    Diagram,
+    Stats,
+    Watch,
+}
+
+impl ToolKind {
+    const ALL: [ToolKind; 4] = [
+        ToolKind::Trace,
+        ToolKind::Diagram,
+        ToolKind::Stats,
+        ToolKind::Watch,
+    ];
+
+    fn display(&self) -> &str {
+        match self {
+            ToolKind::Trace => "Trace",
+            ToolKind::Diagram => "Diagram",
+            ToolKind::Stats => "Stats",
+            ToolKind::Watch => "Watch",
+        }
+    }
+
+    fn command_id(&self) -> i32 {
+        match self {
+            ToolKind::Trace => ID_BTN_TOOL_TRACE,
+            ToolKind::Diagram => ID_BTN_TOOL_DIAGRAM,
+            ToolKind::Stats => ID_BTN_TOOL_STATS,
+            ToolKind::Watch => ID_BTN_TOOL_WATCH,
+        }
+    }
+
+    fn from_command(cmd: i32) -> Option<ToolKind> {
+        ToolKind::ALL.into_iter().find(|k| k.command_id() == cmd)
+    }
+
+    fn from_str(s: &str) -> Option<ToolKind> {
+        ToolKind::ALL.into_iter().find(|k| k.display() == s.trim())
+    }
+}
+
+const TOOL_CLASS_NAME: PCWSTR = w!("TuringToolWindowClass");
+
+unsafe fn toggle_tool_window(owner: HWND, app: &SharedMachine, kind: ToolKind) {
+    let open = app
+        .borrow()
+        .tool_windows
+        .iter()
+        .find(|(k, _)| *k == kind)
+        .map(|&(_, h)| h);
+    match open {
+        Some(h) => {
+            DestroyWindow(h);
+        }
+        None => {
+            open_tool_window(owner, app, kind, None);
+        }
+    }
+}
+
+unsafe fn open_tool_window(
+    owner: HWND,
+    app: &SharedMachine,
+    kind: ToolKind,
+    placement: Option<ToolPlacement>,
+) -> HWND {
+    let Ok(hinst) = GetModuleHandleW(None) else {
+        return HWND::default();
+    };
+    let title = to_wide(kind.display());
+
+    // Cascade new windows along the owner's right edge; a saved placement
+    // that is still on screen is applied once the window exists
+    let mut owner_rc = RECT::default();
+    GetWindowRect(owner, &mut owner_rc);
+    let offset = app.borrow().tool_windows.len() as i32 * 30;
+    let rc = RECT {
+        left: owner_rc.right - 380 + offset,
+        top: owner_rc.top + 40 + offset,
+        right: owner_rc.right - 20 + offset,
+        bottom: owner_rc.top + 340 + offset,
+    };
+    let placement = placement.filter(|p| {
+        !MonitorFromRect(&p.normal_rect, MONITOR_DEFAULTTONULL).is_invalid()
+    });
+
+    // The new window takes ownership of this reference in WM_NCCREATE and
+    // releases it in WM_NCDESTROY
+    let raw = Box::into_raw(Box::new(app.clone()));
+    let hwnd = CreateWindowExW(
+        WS_EX_TOOLWINDOW,
+        TOOL_CLASS_NAME,
+        PCWSTR(title.as_ptr()),
+        WS_OVERLAPPEDWINDOW,
+        rc.left,
+        rc.top,
+        rc.right - rc.left,
+        rc.bottom - rc.top,
+        owner,
+        None,
+        hinst,
+        Some(raw as *const std::ffi::c_void),
+    );
+    if hwnd.0 == 0 {
+        return hwnd;
+    }
+    app.borrow_mut().tool_windows.push((kind, hwnd));
+    match placement {
+        Some(p) => {
+            // Shows the window as well, minimized or maximized if it was
+            let wp = WINDOWPLACEMENT {
+                length: std::mem::size_of::<WINDOWPLACEMENT>() as u32,
+                showCmd: p.show_cmd,
+                rcNormalPosition: p.normal_rect,
+                ..Default::default()
+            };
+            SetWindowPlacement(hwnd, &wp);
+        }
+        None => {
+            let _ = ShowWindow(hwnd, SW_SHOWNORMAL);
+        }
+    }
+    hwnd
+}
+
+// Tool window layout is kept per machine file in a `<file>.layout` sidecar,
+// one `tool <kind> <show> <left> <top> <right> <bottom>` record per open
+// window, as reported by GetWindowPlacement (show command and restored
+// rectangle). Tool windows have WS_EX_TOOLWINDOW, so that rectangle is in
+// screen coordinates, which is also what MonitorFromRect expects.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct ToolPlacement {
+    kind: ToolKind,
+    show_cmd: u32,
+    normal_rect: RECT,
+}
+
+fn layout_path(machine_path: &str) -> String {
+    format!("{}.layout", machine_path)
+}
+
+unsafe fn save_tool_layout(tm: &TuringMachine) {
+    let Some(path) = &tm.file_path else {
+        return;
+    };
+    let layout = layout_path(path);
+    // Only machines that have used tool windows get a sidecar; an existing
+    // one is still rewritten so closing every window is remembered
+    if tm.tool_windows.is_empty() && !std::path::Path::new(&layout).exists() {
+        return;
+    }
+    let mut out = String::new();
+    for &(kind, h) in &tm.tool_windows {
+        let mut wp = WINDOWPLACEMENT {
+            length: std::mem::size_of::<WINDOWPLACEMENT>() as u32,
+            ..Default::default()
+        };
+        if GetWindowPlacement(h, &mut wp).is_ok() {
+            let rc = wp.rcNormalPosition;
+            out.push_str(&format!(
+                "tool\t{}\t{}\t{}\t{}\t{}\t{}\n",
+                kind.display(),
+                wp.showCmd,
+                rc.left,
+                rc.top,
+                rc.right,
+                rc.bottom
+            ));
+        }
+    }
+    // Best effort: a read-only folder shouldn't stop the machine from loading
+    let _ = std::fs::write(layout, out);
+}
+
+fn parse_tool_layout(text: &str) -> Vec<ToolPlacement> {
+    text.lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.trim_end_matches('\r').split('\t').collect();
+            if fields.len() != 7 || fields[0] != "tool" {
+                return None;
+            }
+            let kind = ToolKind::from_str(fields[1])?;
+            // Hidden windows were closed, so only these come back
+            let show_cmd = fields[2].trim().parse::<u32>().ok().filter(|&cmd| {
+                [SW_SHOWNORMAL, SW_SHOWMINIMIZED, SW_SHOWMAXIMIZED]
+                    .iter()
+                    .any(|sw| sw.0 as u32 == cmd)
+            })?;
+            let coord = |i: usize| fields[i].trim().parse::<i32>().ok();
+            let rc = RECT {
+                left: coord(3)?,
+                top: coord(4)?,
+                right: coord(5)?,
+                bottom: coord(6)?,
+            };
+            (rc.right > rc.left && rc.bottom > rc.top).then_some(ToolPlacement {
+                kind,
+                show_cmd,
+                normal_rect: rc,
+            })
+        })
+        .collect()
+}
+
+unsafe fn restore_tool_layout(owner: HWND, app: &SharedMachine) {
+    // Machines without a saved layout keep whatever is open
+    let Some(path) = app.borrow().file_path.clone() else {
+        return;
+    };
+    let Ok(text) = std::fs::read_to_string(layout_path(&path)) else {
+        return;
+    };
+
+    let open: Vec<HWND> = app.borrow().tool_windows.iter().map(|&(_, h)| h).collect();
+    for h in open {
+        DestroyWindow(h);
+    }
+    let mut seen = Vec::new();
+    for placement in parse_tool_layout(&text) {
+        if !seen.contains(&placement.kind) {
+            seen.push(placement.kind);
+            open_tool_window(owner, app, placement.kind, Some(placement));
+        }
+    }
+}
+
+unsafe fn invalidate_tool_windows(tm: &TuringMachine) {
+    for &(_, h) in &tm.tool_windows {
+        InvalidateRect(h, None, true);
+    }
+}
+
+unsafe extern "system" fn tool_wndproc(
+    hwnd: HWND,
+    msg: u32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    match msg {
+        WM_NCCREATE => {
+            let cs = lparam.0 as *const CREATESTRUCTW;
+            SetWindowLongPtrW(hwnd, GWLP_USERDATA, (*cs).lpCreateParams as isize);
+        }
+
+        WM_PAINT => {
+            let app = shared_machine(hwnd);
+            let Some(tm) = app.as_ref().and_then(|a| a.try_borrow().ok()) else {
+                return DefWindowProcW(hwnd, msg, wparam, lparam);
+            };
+            let Some(&(kind, _)) = tm.tool_windows.iter().find(|(_, h)| *h == hwnd) else {
+                return DefWindowProcW(hwnd, msg, wparam, lparam);
+            };
+            let mut ps = PAINTSTRUCT::default();
+            let hdc = BeginPaint(hwnd, &mut ps);
+            let mut rc = RECT::default();
+            GetClientRect(hwnd, &mut rc);
+            let old_font = SelectObject(hdc, tm.ui_font);
+            SetBkMode(hdc, TRANSPARENT);
+
+            match kind {
+                ToolKind::Trace => paint_trace(hdc, &rc, &tm),
+                ToolKind::Diagram => paint_diagram(hdc, &rc, &tm),
+                ToolKind::Stats => draw_lines(hdc, 10, 10, &stats_lines(&tm)),
+                ToolKind::Watch => draw_lines(hdc, 10, 10, &watch_lines(&tm)),
+            }
+
+            SelectObject(hdc, old_font);
+            EndPaint(hwnd, &ps);
+            return LRESULT(0);
+        }
+
+        WM_SIZE => {
+            InvalidateRect(hwnd, None, true);
+        }
+
+        WM_NCDESTROY => {
+            // Callers release their borrow before DestroyWindow, so this must
+            // succeed; a stale handle would be repainted after it is reused
+            if let Some(app) = shared_machine(hwnd) {
+                app.borrow_mut().tool_windows.retain(|&(_, h)| h != hwnd);
+            }
+            detach_machine(hwnd);
+        }
+
+        _ => {}
+    }
+
+    DefWindowProcW(hwnd, msg, wparam, lparam)
+}
+
+const TOOL_LINE_H: i32 = 18;
+
+unsafe fn draw_lines(hdc: HDC, x: i32, y: i32, lines: &[String]) {
+    for (i, line) in lines.iter().enumerate() {
+        let w = to_wide(line);
+        TextOutW(hdc, x, y + i as i32 * TOOL_LINE_H, &w[..w.len() - 1]);
+    }
+}
+
+fn trace_line(e: &TraceEntry) -> String {
+    format!(
+        "{:>5}  {} @ {}: {} → {}, {}  ⇒ {}",
+        e.step,
+        e.state,
+        e.head_pos,
+        e.read_symbol.display(),
+        e.write_symbol.display(),
+        e.direction.display(),
+        e.new_state
+    )
+}
+
+unsafe fn paint_trace(hdc: HDC, rc: &RECT, tm: &TuringMachine) {
+    // Newest steps at the bottom, as many as fit
+    let visible = ((rc.bottom - 20) / TOOL_LINE_H).max(1) as usize;
+    let start = tm.trace.len().saturating_sub(visible);
+    let lines: Vec<String> = if tm.locked {
+        vec!["Trace hidden for locked solutions".to_string()]
+    } else if tm.trace.is_empty() {
+        vec!["No steps yet".to_string()]
+    } else {
+        tm.trace.iter().skip(start).map(trace_line).collect()
+    };
+    draw_lines(hdc, 10, 10, &lines);
+}
+
+fn stats_lines(tm: &TuringMachine) -> Vec<String> {
+    let mut lines = vec![
+        format!("Status: {}", tm.status.display()),
+        format!("Steps: {}", tm.step_count),
+        format!(
+            "Head range: {} .. {} ({} cells)",
+            tm.head_min,
+            tm.head_max,
+            tm.head_max - tm.head_min + 1
+        ),
+        String::new(),
+        "Steps per state:".to_string(),
+    ];
+    let mut visits: Vec<(&String, &u64)> = tm.state_visits.iter().collect();
+    visits.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+    lines.extend(visits.iter().map(|(s, n)| format!("    {}: {}", s, n)));
+
+    lines.push(String::new());
+    if tm.locked {
+        // Which (state, symbol) pairs fired would give the table away
+        lines.push("Transition uses hidden for locked solutions".to_string());
+        return lines;
+    }
+    lines.push("Transition uses:".to_string());
+    let mut hits: Vec<(&(String, Symbol), &u64)> = tm.transition_hits.iter().collect();
+    hits.sort_by(|a, b| b.1.cmp(a.1).then(a.0 .0.cmp(&b.0 .0)));
+    lines.extend(
+        hits.iter()
+            .map(|((s, sym), n)| format!("    ({}, {}): {}", s, sym.display(), n)),
+    );
+    lines
+}
+
+// Blanks on either end are trimmed
+fn tape_contents(tm: &TuringMachine) -> String {
+    tm.tape
+        .iter()
+        .map(|s| s.display())
+        .collect::<String>()
+        .trim_matches('_')
+        .to_string()
+}
+
+fn watch_lines(tm: &TuringMachine) -> Vec<String> {
+    let under_head = tm
+        .head_pos
+        .checked_sub(tm.tape_offset)
+        .and_then(|i| tm.tape.get(i as usize))
+        .copied()
+        .unwrap_or(Symbol::Blank);
+    let written = tape_contents(tm);
+    vec![
+        format!("State: {}", tm.current_state),
+        format!("Head: {}", tm.head_pos),
+        format!("Under head: {}", under_head.display()),
+        format!("Status: {}", tm.status.display()),
+        format!("Steps: {}", tm.step_count),
+        format!(
+            "Tape: {}",
+            if written.is_empty() {
+                "(blank)"
+            } else {
+                &written
+            }
+        ),
+    ]
+}
+
+unsafe fn paint_diagram(hdc: HDC, rc: &RECT, tm: &TuringMachine) {
+    if tm.locked {
+        draw_lines(hdc, 10, 10, &["Diagram hidden for locked solutions".to_string()]);
+        return;
+    }
+
+    let mut states: Vec<&String> = vec![&tm.start_state];
+    for t in &tm.transitions {
+        for s in [&t.current_state, &t.new_state] {
+            if !states.contains(&s) {
+                states.push(s);
+            }
+        }
+    }
+
+    // States on a circle, start state at the top
+    let cx = (rc.right / 2) as f64;
+    let cy = (rc.bottom / 2) as f64;
+    let radius = (cx.min(cy) - 40.0).max(20.0);
+    let node_r = 18.0;
+    let pos_of = |i: usize| {
+        let angle =
+            i as f64 / states.len() as f64 * std::f64::consts::TAU - std::f64::consts::FRAC_PI_2;
+        (cx + radius * angle.cos(), cy + radius * angle.sin())
+    };
+    let index_of = |s: &String| states.iter().position(|x| *x == s).unwrap_or(0);
+
+    // Edges, one per (from, to) pair with all labels joined
+    let mut edges: Vec<(usize, usize, Vec<String>)> = Vec::new();
+    for t in &tm.transitions {
+        let key = (index_of(&t.current_state), index_of(&t.new_state));
+        let label = format!(
+            "{}/{}{}",
+            t.read_symbol.display(),
+            t.write_symbol.display(),
+            t.direction.display()
+        );
+        match edges.iter_mut().find(|(a, b, _)| (*a, *b) == key) {
+            Some(edge) => edge.2.push(label),
+            None => edges.push((key.0, key.1, vec![label])),
+        }
+    }
+    for (from, to, labels) in &edges {
+        let (x1, y1) = pos_of(*from);
+        let label = to_wide(&labels.join(" "));
+        if from == to {
+            // Self loop above the node
+            Ellipse(
+                hdc,
+                (x1 - 10.0) as i32,
+                (y1 - node_r - 20.0) as i32,
+                (x1 + 10.0) as i32,
+                (y1 - node_r + 4.0) as i32,
+            );
+            TextOutW(
+                hdc,
+                (x1 + 12.0) as i32,
+                (y1 - node_r - 24.0) as i32,
+                &label[..label.len() - 1],
+            );
+            continue;
+        }
+        let (x2, y2) = pos_of(*to);
+        let len = ((x2 - x1).powi(2) + (y2 - y1).powi(2)).sqrt().max(1.0);
+        let (ux, uy) = ((x2 - x1) / len, (y2 - y1) / len);
+        let (sx, sy) = (x1 + ux * node_r, y1 + uy * node_r);
+        let (ex, ey) = (x2 - ux * node_r, y2 - uy * node_r);
+        MoveToEx(hdc, sx as i32, sy as i32, None);
+        LineTo(hdc, ex as i32, ey as i32);
+        // Arrow head
+        for side in [-1.0, 1.0] {
+            MoveToEx(hdc, ex as i32, ey as i32, None);
+            LineTo(
+                hdc,
+                (ex - ux * 8.0 - side * uy * 5.0) as i32,
+                (ey - uy * 8.0 + side * ux * 5.0) as i32,
+            );
+        }
+        // Label off to one side so opposite edges don't overlap
+        let (mx, my) = ((sx + ex) / 2.0 - uy * 12.0, (sy + ey) / 2.0 + ux * 12.0);
+        TextOutW(hdc, mx as i32 - 10, my as i32 - 8, &label[..label.len() - 1]);
+    }
+
+    for (i, state) in states.iter().enumerate() {
+        let (x, y) = pos_of(i);
+        let fill = if **state == tm.current_state {
+            COLORREF(0x00FFFF) // Yellow (BGR)
+        } else {
+            COLORREF(0xFFFFFF)
+        };
+        let brush = CreateSolidBrush(fill);
+        let old_brush = SelectObject(hdc, brush);
+        let (l, t, r, b) = (
+            (x - node_r) as i32,
+            (y - node_r) as i32,
+            (x + node_r) as i32,
+            (y + node_r) as i32,
+        );
+        Ellipse(hdc, l, t, r, b);
+        if **state == tm.accept_state || **state == tm.reject_state {
+            Ellipse(hdc, l + 3, t + 3, r - 3, b - 3);
+        }
+        SelectObject(hdc, old_brush);
+        let _ = DeleteObject(brush);
+
+        let name = to_wide(state);
+        let mut text_rc = RECT {
+            left: l,
+            top: t,
+            right: r,
+            bottom: b,
+        };
+        DrawTextW(
+            hdc,
+            &mut name[..name.len() - 1].to_vec(),
+            &mut text_rc,
+            DT_CENTER | DT_VCENTER | DT_SINGLELINE,
+        );
+    }
 }
 
 // ── Create child controls ───────────────────────────────────────────────────
@@ -788,7 +2616,7 @@ unsafe fn create_controls(hwnd: HWND, hinst: HINSTANCE, tm: &mut TuringMachine)
         lv_style,
         10,
         85,
-        960,
+        700,
         265,
         hwnd,
         HMENU(ID_LISTVIEW as isize),
@@ -806,6 +2634,24 @@ unsafe fn create_controls(hwnd: HWND, hinst: HINSTANCE, tm: &mut TuringMachine)
         LPARAM((LVS_EX_FULLROWSELECT | LVS_EX_GRIDLINES) as isize),
     );
 
+    // Group transitions by current state (collapsible headers). Older list
+    // views ignore the enable message, so ask whether it took effect
+    SendMessageW(tm.h_listview, LVM_ENABLEGROUPVIEW, WPARAM(1), LPARAM(0));
+    tm.group_view =
+        SendMessageW(tm.h_listview, LVM_ISGROUPVIEWENABLED, WPARAM(0), LPARAM(0)).0 != 0;
+
+    // Icon shown on rows with open TODOs
+    let himl = ImageList_Create(16, 16, ILC_COLOR32 | ILC_MASK, 1, 1);
+    if let Ok(icon) = LoadIconW(None, IDI_INFORMATION) {
+        ImageList_ReplaceIcon(himl, -1, icon);
+    }
+    SendMessageW(
+        tm.h_listview,
+        LVM_SETIMAGELIST,
+        WPARAM(LVSIL_SMALL as usize),
+        LPARAM(himl.0),
+    );
+
     // Add columns
     let col_headers = ["Current State", "Read", "New State", "Write", "Dir"];
     let col_widths = [180, 80, 180, 80, 80];
@@ -827,6 +2673,42 @@ unsafe fn create_controls(hwnd: HWND, hinst: HINSTANCE, tm: &mut TuringMachine)
         );
     }
 
+    // ── TODO Pane (x=720..1000, y=85..350) ──
+    create_static(hwnd, hinst, "TODOs:", 720, 85, 80, 18, font);
+    tm.h_todo_list = CreateWindowExW(
+        WS_EX_CLIENTEDGE,
+        w!("LISTBOX"),
+        w!(""),
+        WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | WS_VSCROLL.0 | LBS_NOINTEGRALHEIGHT as u32),
+        720,
+        105,
+        280,
+        175,
+        hwnd,
+        HMENU(ID_TODO_LIST as isize),
+        hinst,
+        None,
+    );
+    send_font(tm.h_todo_list, font);
+    tm.h_todo_edit = CreateWindowExW(
+        WS_EX_CLIENTEDGE,
+        w!("EDIT"),
+        w!(""),
+        WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | ES_AUTOHSCROLL as u32),
+        720,
+        285,
+        280,
+        24,
+        hwnd,
+        HMENU(ID_TODO_EDIT as isize),
+        hinst,
+        None,
+    );
+    send_font(tm.h_todo_edit, font);
+    create_button(hwnd, hinst, "+ Row", 720, 315, 90, 28, ID_BTN_TODO_ROW, font);
+    create_button(hwnd, hinst, "+ State", 815, 315, 90, 28, ID_BTN_TODO_STATE, font);
+    create_button(hwnd, hinst, "Done", 910, 315, 90, 28, ID_BTN_TODO_DONE, font);
+
     // ── Transition Editor (y=355..430) ──
     let editor_y = 355;
     let label_h = 18;
@@ -892,6 +2774,30 @@ unsafe fn create_controls(hwnd: HWND, hinst: HINSTANCE, tm: &mut TuringMachine)
     create_button(hwnd, hinst, "Add", 10, btn_y, 80, 28, ID_BTN_ADD, font);
     create_button(hwnd, hinst, "Update", 100, btn_y, 80, 28, ID_BTN_UPDATE, font);
     create_button(hwnd, hinst, "Delete", 190, btn_y, 80, 28, ID_BTN_DELETE, font);
+    create_button(
+        hwnd,
+        hinst,
+        "Export Locked...",
+        700,
+        btn_y,
+        120,
+        28,
+        ID_BTN_EXPORT_LOCKED,
+        font,
+    );
+    create_button(
+        hwnd,
+        hinst,
+        "Export .xlsx...",
+        580,
+        btn_y,
+        110,
+        28,
+        ID_BTN_EXPORT_XLSX,
+        font,
+    );
+    create_button(hwnd, hinst, "Open...", 830, btn_y, 80, 28, ID_BTN_OPEN, font);
+    create_button(hwnd, hinst, "Save...", 920, btn_y, 80, 28, ID_BTN_SAVE, font);
 
     // ── Control Area (y=435..500) ──
     let ctrl_area_y = 440;
@@ -980,7 +2886,7 @@ unsafe fn create_controls(hwnd: HWND, hinst: HINSTANCE, tm: &mut TuringMachine)
         WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0), // SS_LEFT = 0
         10,
         505,
-        970,
+        580,
         25,
         hwnd,
         HMENU(ID_STATUS_LABEL as isize),
@@ -988,6 +2894,12 @@ unsafe fn create_controls(hwnd: HWND, hinst: HINSTANCE, tm: &mut TuringMachine)
         None,
     );
     send_font(tm.h_status_label, font);
+
+    // Tool window toggles
+    for (i, kind) in ToolKind::ALL.iter().enumerate() {
+        let x = 600 + i as i32 * 100;
+        create_button(hwnd, hinst, kind.display(), x, 500, 95, 28, kind.command_id(), font);
+    }
 }
 
 unsafe fn create_static(
@@ -1114,6 +3026,16 @@ fn main() -> Result<()> {
         };
         RegisterClassW(&wc);
 
+        let tool_wc = WNDCLASSW {
+            lpfnWndProc: Some(tool_wndproc),
+            hInstance: hinstance.into(),
+            lpszClassName: TOOL_CLASS_NAME,
+            hbrBackground: HBRUSH((COLOR_WINDOW.0 + 1) as isize),
+            hCursor: LoadCursorW(None, IDC_ARROW)?,
+            ..Default::default()
+        };
+        RegisterClassW(&tool_wc);
+
         let hwnd = CreateWindowExW(
             WINDOW_EX_STYLE(0),
             class_name,
@@ -1129,8 +3051,7 @@ fn main() -> Result<()> {
             None,
         );
 
-        // Create TuringMachine on heap
-        let mut tm = Box::new(TuringMachine::new());
+        let mut tm = TuringMachine::new();
 
         // Create Segoe UI font for all controls and paint
         let mut face_name = [0u16; 32];
@@ -1157,9 +3078,9 @@ fn main() -> Result<()> {
         // Create all child controls
         create_controls(hwnd, hinstance.into(), &mut tm);
 
-        // Store pointer in window user data
-        let raw = Box::into_raw(tm);
-        SetWindowLongPtrW(hwnd, GWLP_USERDATA, raw as isize);
+        // Share the machine with the main window (and later tool windows)
+        let app: SharedMachine = Rc::new(RefCell::new(tm));
+        attach_machine(hwnd, &app);
 
         let _ = ShowWindow(hwnd, SW_SHOW);
         UpdateWindow(hwnd);
@@ -1173,3 +3094,207 @@ fn main() -> Result<()> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transition(cur: &str, read: Symbol, new: &str, bp: bool) -> Transition {
+        Transition {
+            current_state: cur.to_string(),
+            read_symbol: read,
+            new_state: new.to_string(),
+            write_symbol: Symbol::One,
+            direction: Direction::Right,
+            has_breakpoint: bp,
+        }
+    }
+
+    fn sample_machine() -> TuringMachine {
+        let mut tm = TuringMachine::new();
+        tm.start_state = "q0".to_string();
+        tm.transitions = vec![
+            transition("q0", Symbol::Zero, "q1", true),
+            transition("q1", Symbol::Blank, "qa", false),
+        ];
+        tm.state_breakpoints.insert("q1".to_string());
+        tm.todos = vec![
+            Todo {
+                target: TodoTarget::State("q0".to_string()),
+                text: "check empty input".to_string(),
+            },
+            Todo {
+                target: TodoTarget::Transition("q1".to_string(), Symbol::Blank),
+                text: "should this reject?".to_string(),
+            },
+        ];
+        tm
+    }
+
+    #[test]
+    fn machine_file_round_trip() {
+        let tm = sample_machine();
+        let text = tm.serialize();
+        let mut loaded = TuringMachine::new();
+        loaded.group_states.push("q0".to_string());
+        loaded.collapsed_groups.insert("q0".to_string());
+        loaded.deserialize(&text).unwrap();
+        assert!(loaded.group_states.is_empty());
+        assert!(loaded.collapsed_groups.is_empty());
+
+        assert_eq!(loaded.start_state, "q0");
+        assert_eq!(loaded.transitions.len(), 2);
+        assert!(loaded.transitions[0].has_breakpoint);
+        assert!(!loaded.transitions[1].has_breakpoint);
+        assert_eq!(loaded.transitions[1].read_symbol, Symbol::Blank);
+        assert!(loaded.state_breakpoints.contains("q1"));
+        assert_eq!(loaded.todos.len(), 2);
+        assert_eq!(loaded.todos[0].target, TodoTarget::State("q0".to_string()));
+        assert_eq!(
+            loaded.todos[1].target,
+            TodoTarget::Transition("q1".to_string(), Symbol::Blank)
+        );
+        assert_eq!(loaded.todos[1].text, "should this reject?");
+        assert_eq!(loaded.serialize(), text);
+    }
+
+    #[test]
+    fn machine_file_rejects_bad_lines() {
+        let mut tm = sample_machine();
+        let err = tm
+            .deserialize("version\t1\ntransition\tq0\t2\tq1\t1\tR\n")
+            .unwrap_err();
+        assert!(err.starts_with("Line 2:"), "{}", err);
+        assert!(tm.deserialize("version\t2\nstart\tq0\n").is_err());
+        // A failed load leaves the machine untouched
+        assert_eq!(tm.transitions.len(), 2);
+
+        assert!(tm.deserialize("start\tq0\nfuture_record\tx\n").is_ok());
+        assert!(tm.transitions.is_empty());
+    }
+
+    #[test]
+    fn state_names_lose_separators() {
+        assert_eq!(clean_state_name(" q\t0\r\n "), "q 0");
+    }
+
+    #[test]
+    fn hex_round_trip() {
+        let bytes = [0x00, 0x0f, 0xa5, 0xff];
+        assert_eq!(to_hex(&bytes), "000fa5ff");
+        assert_eq!(from_hex("000fa5ff").unwrap(), bytes);
+        assert_eq!(from_hex("000FA5FF").unwrap(), bytes);
+        assert_eq!(from_hex("").unwrap(), Vec::<u8>::new());
+        assert!(from_hex("abc").is_none());
+        assert!(from_hex("zz").is_none());
+        assert!(from_hex("+f").is_none());
+    }
+
+    #[test]
+    fn tool_layout_parsing() {
+        let text = "tool\tTrace\t1\t10\t20\t310\t220\r\n\
+                    tool\tStats\t3\t0\t0\t400\t300\n\
+                    tool\tWatch\t0\t0\t0\t400\t300\n\
+                    tool\tDiagram\t1\t50\t50\t50\t90\n\
+                    tool\tBogus\t1\t0\t0\t10\t10\n\
+                    tool\tTrace\t1\t0\t0\t10\n\
+                    garbage\n";
+        let placements = parse_tool_layout(text);
+        assert_eq!(
+            placements,
+            vec![
+                ToolPlacement {
+                    kind: ToolKind::Trace,
+                    show_cmd: SW_SHOWNORMAL.0 as u32,
+                    normal_rect: RECT {
+                        left: 10,
+                        top: 20,
+                        right: 310,
+                        bottom: 220,
+                    },
+                },
+                ToolPlacement {
+                    kind: ToolKind::Stats,
+                    show_cmd: SW_SHOWMAXIMIZED.0 as u32,
+                    normal_rect: RECT {
+                        left: 0,
+                        top: 0,
+                        right: 400,
+                        bottom: 300,
+                    },
+                },
+            ]
+        );
+        assert!(parse_tool_layout("").is_empty());
+    }
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn column_names() {
+        assert_eq!(column_name(0), "A");
+        assert_eq!(column_name(25), "Z");
+        assert_eq!(column_name(26), "AA");
+        assert_eq!(column_name(701), "ZZ");
+        assert_eq!(column_name(702), "AAA");
+    }
+
+    #[test]
+    fn xml_escape_drops_illegal_characters() {
+        assert_eq!(xml_escape("a<b & \"c\">"), "a&lt;b &amp; &quot;c&quot;&gt;");
+        assert_eq!(xml_escape("x\u{0}\u{1b}\ty\r\nz\u{ffff}"), "x\ty\r\nz");
+    }
+
+    #[test]
+    fn frozen_rows_are_optional() {
+        let rows = vec![vec![text_cell("Head")], vec![Cell::Number(1)]];
+        assert!(sheet_xml(&rows, &[0], 1, &[10]).contains("ySplit=\"1\" topLeftCell=\"A2\""));
+        assert!(!sheet_xml(&rows, &[0], 0, &[10]).contains("<pane"));
+    }
+
+    fn read_u16(b: &[u8], at: usize) -> usize {
+        u16::from_le_bytes([b[at], b[at + 1]]) as usize
+    }
+
+    fn read_u32(b: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes([b[at], b[at + 1], b[at + 2], b[at + 3]])
+    }
+
+    #[test]
+    fn zip_stored_round_trip() {
+        let entries = [
+            ("a.txt", b"hello".to_vec()),
+            ("dir/empty.xml", Vec::new()),
+            ("b.bin", (0..=255u8).collect()),
+        ];
+        let zip = zip_stored(&entries);
+
+        // Walk the central directory from the end record back to each entry
+        let end = zip.len() - 22;
+        assert_eq!(read_u32(&zip, end), 0x0605_4b50);
+        assert_eq!(read_u16(&zip, end + 10), entries.len());
+        let mut at = read_u32(&zip, end + 16) as usize;
+        for (name, data) in &entries {
+            assert_eq!(read_u32(&zip, at), 0x0201_4b50);
+            let crc = read_u32(&zip, at + 16);
+            let name_len = read_u16(&zip, at + 28);
+            let offset = read_u32(&zip, at + 42) as usize;
+            assert_eq!(&zip[at + 46..at + 46 + name_len], name.as_bytes());
+            at += 46 + name_len;
+
+            assert_eq!(read_u32(&zip, offset), 0x0403_4b50);
+            assert_eq!(read_u16(&zip, offset + 8), 0); // stored
+            let size = read_u32(&zip, offset + 18) as usize;
+            let local_name_len = read_u16(&zip, offset + 26);
+            let start = offset + 30 + local_name_len + read_u16(&zip, offset + 28);
+            let stored = &zip[start..start + size];
+            assert_eq!(stored, &data[..]);
+            assert_eq!(crc32(stored), crc);
+        }
+        assert_eq!(at, end);
+    }
+}